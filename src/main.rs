@@ -2,22 +2,40 @@
 #![feature(in_band_lifetimes)]
 
 use std::cmp::{Ordering};
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap};
 use std::ops::{Range, RangeInclusive};
 use std::fmt::{Display, Formatter, Error};
-use std::io;
-use std::io::prelude::*;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-fn pause() {
-    let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
+// A tiny xorshift64 generator, so the puzzle generator needs no RNG crate.
+struct Rng(u64);
 
-    // We want the cursor to stay at the end of the line, so we print without a newline and flush manually.
-    write!(stdout, "Press any key to continue...").unwrap();
-    stdout.flush().unwrap();
+impl Rng {
+    fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64;
+        Rng(nanos | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
 
-    // Read a single byte and discard
-    let _ = stdin.read(&mut [0u8]).unwrap();
+    fn shuffle<T>(&mut self, v: &mut [T]) {
+        for i in (1..v.len()).rev() {
+            let j = self.below(i + 1);
+            v.swap(i, j);
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -30,6 +48,42 @@ pub enum SudokuResult{
     MultipleCandidates(Vec<SudokuSquare>),
 }
 
+// Candidate set as a bitmask: bit i means symbol i is still possible.
+type Candidates = u32;
+
+// A pure-propagation puzzle scores propagation_solve_rate 1.0 with no guesses.
+#[derive(Debug, Clone)]
+pub struct Difficulty {
+    pub propagation_solve_rate: f64,
+    pub guesses: usize,
+    pub max_depth: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct BoardSpec {
+    pub rows: usize,
+    pub cols: usize,
+    pub box_w: usize,
+    pub box_h: usize,
+    pub symbols: String,
+}
+
+impl BoardSpec {
+    pub fn classic() -> Self {
+        Self {
+            rows: 9,
+            cols: 9,
+            box_w: 3,
+            box_h: 3,
+            symbols: String::from("123456789"),
+        }
+    }
+
+    pub fn symbol_count(&self) -> usize {
+        self.symbols.chars().count()
+    }
+}
+
 pub trait RangeInterval {
     fn interval(&self) -> (usize, usize);
 }
@@ -58,12 +112,13 @@ fn create_square_vec(rows: Box<dyn RangeInterval>, cols: Box<dyn RangeInterval>)
     v
 }
 
-fn create_unitlist(r: Range<usize>, c: Range<usize>) -> Vec<Vec<SudokuSquare>> {
+fn create_unitlist(spec: &BoardSpec) -> Vec<Vec<SudokuSquare>> {
 
-    let max_rows = r.end;
-    let max_cols = c.end;
+    let max_rows = spec.rows;
+    let max_cols = spec.cols;
 
-    let mut v = Vec::with_capacity(max_rows * 3);
+    let boxes = (max_rows / spec.box_h) * (max_cols / spec.box_w);
+    let mut v = Vec::with_capacity(max_rows + max_cols + boxes);
 
     for row in 0..max_rows {
         v.push(create_square_vec(Box::new(row..=row),
@@ -75,18 +130,62 @@ fn create_unitlist(r: Range<usize>, c: Range<usize>) -> Vec<Vec<SudokuSquare>> {
                                    Box::new(col..=col)));
     }
 
-    let matrices = vec![(0..=2), (3..=5), (6..=8)];
-
-    for m in 0..matrices.len(){
-        for n in 0..matrices.len(){
-            v.push(create_square_vec(Box::new(matrices[m].clone()),
-                                     Box::new(matrices[n].clone())));
+    // Walk the grid in box_h-tall, box_w-wide steps instead of fixed (0..=2) triples.
+    for box_row in (0..max_rows).step_by(spec.box_h) {
+        for box_col in (0..max_cols).step_by(spec.box_w) {
+            v.push(create_square_vec(Box::new(box_row..=box_row + spec.box_h - 1),
+                                     Box::new(box_col..=box_col + spec.box_w - 1)));
         }
     }
     // Time to return
     v
 }
 
+// The two main diagonals: the extra units for an X-Sudoku.
+pub fn diagonal_units(spec: &BoardSpec) -> Vec<Vec<SudokuSquare>> {
+    let n = spec.rows.min(spec.cols);
+    let mut v = Vec::with_capacity(2);
+    v.push((0..n).map(|i| SudokuSquare(i, i)).collect());
+    v.push((0..n).map(|i| SudokuSquare(i, n - 1 - i)).collect());
+    v
+}
+
+// The four windoku windows, inset one cell from each corner.
+pub fn windoku_units(spec: &BoardSpec) -> Vec<Vec<SudokuSquare>> {
+    let mut v = Vec::with_capacity(4);
+    for row in [1, spec.box_h + 2] {
+        for col in [1, spec.box_w + 2] {
+            v.push(create_square_vec(Box::new(row..=row + spec.box_h - 1),
+                                     Box::new(col..=col + spec.box_w - 1)));
+        }
+    }
+    v
+}
+
+// Anti-knight exclusion: each knight's-move pair as a two-square unit.
+pub fn knight_move_units(spec: &BoardSpec) -> Vec<Vec<SudokuSquare>> {
+    let offsets: [(i64, i64); 8] = [(1, 2), (2, 1), (2, -1), (1, -2),
+                                    (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+    let mut v = Vec::new();
+    for row in 0..spec.rows {
+        for col in 0..spec.cols {
+            for (dr, dc) in offsets.iter() {
+                let nr = row as i64 + dr;
+                let nc = col as i64 + dc;
+                if nr < 0 || nc < 0 || nr >= spec.rows as i64 || nc >= spec.cols as i64 {
+                    continue;
+                }
+                // Emit each pair once by keeping the lexicographically smaller square first.
+                let (a, b) = (SudokuSquare(row, col), SudokuSquare(nr as usize, nc as usize));
+                if a < b {
+                    v.push(vec![a, b]);
+                }
+            }
+        }
+    }
+    v
+}
+
 fn create_unit_dictionary(unitlist: &Vec<Vec<SudokuSquare>>) -> HashMap<SudokuSquare, SudokuUnit> {
     let mut unitmap: HashMap<SudokuSquare, SudokuUnit> = HashMap::new();
     for v in unitlist{
@@ -106,11 +205,11 @@ fn create_unit_dictionary(unitlist: &Vec<Vec<SudokuSquare>>) -> HashMap<SudokuSq
 }
 
 fn create_peers_dictionary(unit_dict: &HashMap<SudokuSquare, SudokuUnit>)
-                           -> HashMap<SudokuSquare, HashSet<SudokuSquare>> {
-    let mut peers: HashMap<SudokuSquare, HashSet<SudokuSquare>> = HashMap::new();
+                           -> HashMap<SudokuSquare, BTreeSet<SudokuSquare>> {
+    let mut peers: HashMap<SudokuSquare, BTreeSet<SudokuSquare>> = HashMap::new();
 
     for unit in unit_dict.iter(){
-        let mut set = HashSet::new();
+        let mut set = BTreeSet::new();
         for &v in &unit.1.unitvec {
             set.extend( v.clone().drain_filter(|&mut x| x != *unit.0) );
         }
@@ -145,16 +244,18 @@ struct SudokuUnit<'a>{
 }
 
 struct GameSetup<'a>{
+    spec: &'a BoardSpec,
     squares:  &'a Vec<Vec<SudokuSquare>>,
     units:  &'a HashMap<SudokuSquare, SudokuUnit<'a>>,
-    peers:  &'a HashMap<SudokuSquare, HashSet<SudokuSquare>>,
+    peers:  &'a HashMap<SudokuSquare, BTreeSet<SudokuSquare>>,
     sorted_squares: Vec<SudokuSquare>,
 }
 
 impl GameSetup<'a>{
-    fn new(squares: &'a Vec<Vec<SudokuSquare>>,
+    fn new(spec: &'a BoardSpec,
+           squares: &'a Vec<Vec<SudokuSquare>>,
            units: &'a HashMap<SudokuSquare, SudokuUnit>,
-           peers: &'a HashMap<SudokuSquare, HashSet<SudokuSquare>>) -> Self {
+           peers: &'a HashMap<SudokuSquare, BTreeSet<SudokuSquare>>) -> Self {
 
         let mut _sorted = vec![];
         for (k, _v) in units.iter() {
@@ -164,88 +265,126 @@ impl GameSetup<'a>{
         _sorted.sort();
 
         Self {
+            spec,
             squares,
             units,
             peers,
             sorted_squares: _sorted,
         }
     }
+
+    // Variant Sudokus (diagonal, windoku, anti-knight, ...) fold their extra
+    // constraint groups into `squares` before `units`/`peers` are derived, so
+    // `assign`/`eliminate` solve them through the same units and peers. This
+    // just checks the caller actually did that: every group in `extra` must
+    // already be among the units of each of its squares.
+    pub fn with_extra_units(spec: &'a BoardSpec,
+                            squares: &'a Vec<Vec<SudokuSquare>>,
+                            units: &'a HashMap<SudokuSquare, SudokuUnit>,
+                            peers: &'a HashMap<SudokuSquare, BTreeSet<SudokuSquare>>,
+                            extra: &Vec<Vec<SudokuSquare>>) -> Self {
+        for group in extra {
+            for square in group {
+                let folded = units.get(square)
+                    .map_or(false, |u| u.unitvec.iter().any(|unit| *unit == group));
+                assert!(folded,
+                    "extra unit square not in units: build units/peers from \
+                     squares after extending it with the extra groups");
+            }
+        }
+        Self::new(spec, squares, units, peers)
+    }
 }
 
 #[derive(Clone)]
 struct Game<'a> {
     game_setup: &'a GameSetup<'a>,
-    stats: HashMap<SudokuSquare, String>,
+    // One candidate bitmask per cell, indexed `row * cols + col`.
+    stats: Vec<Candidates>,
 }
 
 impl Game<'a> {
     pub fn new(game_setup: &'a GameSetup) -> Self {
         Self {
             game_setup,
-            stats: HashMap::new(),
+            stats: Vec::new(),
         }
     }
 
-    pub fn init_game_with_values(&mut self, values: &str) {
-        let value_chars = values.chars();
-        // assert_eq!(value_chars.count(), self.game_setup.squares.len());
-
-        let mut square_set = BTreeSet::new();
+    fn index(&self, square: &SudokuSquare) -> usize {
+        square.0 * self.game_setup.spec.cols + square.1
+    }
 
-        for unit in &self.game_setup.sorted_squares {
-            square_set.insert(unit.clone() );
-            self.stats.insert(unit.clone(), String::from("123456789"));
+    fn bit_of(&self, c: char) -> Candidates {
+        match self.game_setup.spec.symbols.find(c) {
+            Some(i) => 1 << i,
+            None => 0,
         }
-        // Throw in a small sanity check.
-        // let char_length = value_chars.by_ref().count();
-        // assert_eq!(square_set.len(), char_length);
+    }
 
-        let mut it = value_chars.into_iter();
-        for set_element in square_set {
+    fn full_mask(&self) -> Candidates {
+        let n = self.game_setup.spec.symbol_count();
+        if n >= Candidates::BITS as usize { !0 } else { (1 << n) - 1 }
+    }
+
+    pub fn init_game_with_values(&mut self, values: &str) {
+        let full = self.full_mask();
+        self.stats = vec![full; self.game_setup.spec.rows * self.game_setup.spec.cols];
+
+        // sorted_squares is row-major, matching the flat reading order of values.
+        let mut it = values.chars();
+        for square in self.game_setup.sorted_squares.clone() {
             let c = it.next().unwrap();
-            if matches!(c, '1'..='9') {
-                self.assign(&set_element, c);
+            if self.game_setup.spec.symbols.contains(c) {
+                let _ = self.assign(&square, c);
             }
         }
     }
 
     pub fn assign(&mut self, square: &SudokuSquare, c: char) -> Result<(), SudokuError>{
-        let unit_stored = self.stats.get_key_value(square).unwrap();
-        let mut values = unit_stored.1.clone();
-        values = values.replace(c, "");
-        for c in values.chars(){
-            self.eliminate(&square, c)?;
+        self.assign_bit(square, self.bit_of(c))
+    }
+
+    fn assign_bit(&mut self, square: &SudokuSquare, d: Candidates) -> Result<(), SudokuError>{
+        let mut others = self.stats[self.index(square)] & !d;
+        while others != 0 {
+            let low = others & others.wrapping_neg();
+            self.eliminate(square, low)?;
+            others &= !low;
         }
         Ok(())
     }
 
-    fn eliminate(&mut self, square: &SudokuSquare, c: char) -> Result<(), SudokuError>{
-        let unit_stored = self.stats.get_key_value(square).unwrap();
-        if !unit_stored.1.contains(c) {
+    fn eliminate(&mut self, square: &SudokuSquare, d: Candidates) -> Result<(), SudokuError>{
+        let i = self.index(square);
+        if self.stats[i] & d == 0 {
             return Ok(());
-        } else{
-            let new_value = unit_stored.1.replace(c, "");
-            if new_value.len() == 0 {
-                return Err(SudokuError::NoRemainingValues);
-            }
-            self.stats.insert(square.clone(), new_value);
+        }
+        self.stats[i] &= !d;
+        let mask = self.stats[i];
+        if mask == 0 {
+            return Err(SudokuError::NoRemainingValues);
         }
 
-        let unit_stored = self.stats.get_key_value(square).unwrap();
-        if unit_stored.1.len() == 1 {
-            let last_char_remaining = unit_stored.1.chars().next().unwrap();
-            let peers = self.game_setup.peers.get(&square).unwrap();
+        // A square reduced to one candidate forces that value out of all its peers.
+        if mask.count_ones() == 1 {
+            let peers = self.game_setup.peers.get(&square).unwrap().clone();
             for p in peers.iter() {
-                self.eliminate(p, last_char_remaining)?;
+                self.eliminate(p, mask)?;
             }
         }
 
         for unit in self.game_setup.units.get(&square) {
             for solidary_unit in &unit.unitvec {
-                match self.count_places_for_value(solidary_unit, c){
+                // Only a unit holding every symbol must place `d` somewhere in it;
+                // shorter groups (e.g. knight-move pairs) only exclude repeats.
+                if solidary_unit.len() != self.game_setup.spec.symbol_count() {
+                    continue;
+                }
+                match self.count_places_for_value(solidary_unit, d){
                     Ok(SudokuResult::MultipleCandidates(_)) => continue,
                     Ok(SudokuResult::OneCandidate(candidate)) =>
-                        return self.assign(&candidate, c),
+                        return self.assign_bit(&candidate, d),
                     Err(error) => return Err(error),
                 }
             }
@@ -254,13 +393,12 @@ impl Game<'a> {
         Ok(())
     }
 
-    fn count_places_for_value(&self, square_vec: &Vec<SudokuSquare>, c: char ) ->
+    fn count_places_for_value(&self, square_vec: &Vec<SudokuSquare>, d: Candidates ) ->
     Result<SudokuResult, SudokuError>{
         let mut candidates = vec![];
         for s in square_vec {
-            match self.stats.get_key_value(s).unwrap().1.contains(c){
-                true => candidates.push(s.clone()),
-                _ => (),
+            if self.stats[self.index(s)] & d != 0 {
+                candidates.push(s.clone());
             }
         }
 
@@ -271,22 +409,89 @@ impl Game<'a> {
         }
     }
 
-    pub fn search(&self) {
-        let solved_status = self.is_solved();
-        match solved_status {
+    pub fn solve(&self) -> Option<Game<'a>> {
+        self.solutions(1).into_iter().next()
+    }
+
+    pub fn solutions(&self, limit: usize) -> Vec<Game<'a>> {
+        let mut found = Vec::new();
+        self.search(limit, &mut found);
+        found
+    }
+
+    pub fn is_unique(&self) -> bool {
+        self.solutions(2).len() == 1
+    }
+
+    // Call before any search: 1.0 means init_game_with_values alone solved it.
+    pub fn propagation_solve_rate(&self) -> f64 {
+        let solved = self.stats.iter().filter(|m| m.count_ones() == 1).count();
+        solved as f64 / self.stats.len() as f64
+    }
+
+    pub fn grade(&self) -> Option<(Game<'a>, Difficulty)> {
+        let propagation_solve_rate = self.propagation_solve_rate();
+        let mut guesses = 0;
+        let mut max_depth = 0;
+        let mut solution = None;
+        self.search_graded(0, &mut guesses, &mut max_depth, &mut solution);
+        solution.map(|s| (s, Difficulty { propagation_solve_rate, guesses, max_depth }))
+    }
+
+    // Bits of `mask` ordered by symbol value rather than bit position, so
+    // branching tries the same symbols in the same order no matter where they
+    // happen to sit in spec.symbols (see difficulty_is_independent_of_symbol_ordering).
+    fn ordered_candidates(&self, mask: Candidates) -> Vec<Candidates> {
+        let mut bits: Vec<(char, Candidates)> = self.game_setup.spec.symbols.chars().enumerate()
+            .filter_map(|(i, c)| { let bit = 1 << i; if mask & bit != 0 { Some((c, bit)) } else { None } })
+            .collect();
+        bits.sort_by_key(|&(c, _)| c);
+        bits.into_iter().map(|(_, bit)| bit).collect()
+    }
+
+    // Stops at the first solution, like `search`, so grading a valid but
+    // under-constrained puzzle doesn't have to exhaust the whole search tree.
+    fn search_graded(&self, depth: usize, guesses: &mut usize, max_depth: &mut usize,
+                     solution: &mut Option<Game<'a>>) {
+        if depth > *max_depth {
+            *max_depth = depth;
+        }
+        match self.is_solved() {
             Err(_error) => (),
-            Ok((true, _)) => {
-                println!("{}", self);
-                pause()
+            Ok((true, _)) => *solution = Some(self.clone()),
+            Ok((false, candidate)) => {
+                // More than one value was possible here: a branch point.
+                *guesses += 1;
+                let mask = self.stats[self.index(&candidate)];
+                for bit in self.ordered_candidates(mask) {
+                    let mut game_branch = self.clone();
+                    if game_branch.assign_bit(&candidate, bit).is_ok() {
+                        game_branch.search_graded(depth + 1, guesses, max_depth, solution);
+                        if solution.is_some() {
+                            return;
+                        }
+                    }
+                }
             },
+        }
+    }
+
+    fn search(&self, limit: usize, found: &mut Vec<Game<'a>>) {
+        if found.len() >= limit {
+            return;
+        }
+        match self.is_solved() {
+            Err(_error) => (),
+            Ok((true, _)) => found.push(self.clone()),
             Ok((false, candidate)) => {
-                let candidate = self.stats.get_key_value(&candidate).unwrap();
-                for c in candidate.1.chars(){
+                let mut mask = self.stats[self.index(&candidate)];
+                while mask != 0 && found.len() < limit {
+                    let low = mask & mask.wrapping_neg();
                     let mut game_branch = self.clone();
-                    let assign_result = game_branch.assign(candidate.0, c);
-                    if assign_result.is_ok() {
-                        game_branch.search();
+                    if game_branch.assign_bit(&candidate, low).is_ok() {
+                        game_branch.search(limit, found);
                     }
+                    mask &= !low;
                 }
             },
         }
@@ -294,40 +499,114 @@ impl Game<'a> {
 
     fn is_solved(&self) -> Result<(bool, SudokuSquare), SudokuError> {
 
-        let min_candidates = self.stats.iter()
-            .fold((0, 9, SudokuSquare(0,0)), |acc, square|
-                {
-                    let len = square.1.len();
-                    match len {
-                        0 => (acc.0, 0, square.0.clone()),
-                        1 => (acc.0 + 1, acc.1, acc.2),
-                        _ if &len < &acc.1 => (acc.0, *&len, square.0.clone()),
-                        _ => acc,
-                    }
-                });
-
-        if min_candidates.1 == 0 {
-            return Err(SudokuError::NoRemainingValues);
+        let cols = self.game_setup.spec.cols;
+        let symbol_count = self.game_setup.spec.symbol_count();
+        let mut solved = 0;
+        let mut min = symbol_count + 1;
+        let mut min_square = SudokuSquare(0, 0);
+
+        for (i, &mask) in self.stats.iter().enumerate() {
+            let len = mask.count_ones() as usize;
+            match len {
+                0 => return Err(SudokuError::NoRemainingValues),
+                1 => solved += 1,
+                _ if len < min => { min = len; min_square = SudokuSquare(i / cols, i % cols); },
+                _ => (),
+            }
         }
 
-        return if min_candidates.0 == self.stats.keys().len() {
-            Ok((true, min_candidates.2))
+        return if solved == self.stats.len() {
+            Ok((true, min_square))
         } else {
-            Ok((false, min_candidates.2))
+            Ok((false, min_square))
+        }
+
+    }
+
+    pub fn generate(game_setup: &'a GameSetup, min_clues: usize) -> Game<'a> {
+        let mut rng = Rng::from_time();
+
+        let mut empty = Game::new(game_setup);
+        empty.stats = vec![empty.full_mask();
+                           game_setup.spec.rows * game_setup.spec.cols];
+        let solved = empty.random_full_solution(&mut rng)
+            .expect("an empty board always has at least one solution");
+
+        // '.' marks a blanked cell; it is never part of any symbol alphabet.
+        let mut clues: Vec<char> = game_setup.sorted_squares.iter()
+            .map(|sq| solved.render(solved.stats[solved.index(sq)]).chars().next().unwrap())
+            .collect();
+
+        let mut positions: Vec<usize> = (0..clues.len()).collect();
+        rng.shuffle(&mut positions);
+
+        let mut clue_count = clues.len();
+        for pos in positions {
+            if clue_count <= min_clues {
+                break;
+            }
+            let saved = clues[pos];
+            clues[pos] = '.';
+
+            let mut trial = Game::new(game_setup);
+            trial.init_game_with_values(&clues.iter().collect::<String>());
+            if trial.is_unique() {
+                clue_count -= 1;
+            } else {
+                clues[pos] = saved;
+            }
+        }
+
+        let mut puzzle = Game::new(game_setup);
+        puzzle.init_game_with_values(&clues.iter().collect::<String>());
+        puzzle
+    }
+
+    fn random_full_solution(&self, rng: &mut Rng) -> Option<Game<'a>> {
+        match self.is_solved() {
+            Err(_error) => None,
+            Ok((true, _)) => Some(self.clone()),
+            Ok((false, candidate)) => {
+                let mut bits = Vec::new();
+                let mut mask = self.stats[self.index(&candidate)];
+                while mask != 0 {
+                    let low = mask & mask.wrapping_neg();
+                    bits.push(low);
+                    mask &= !low;
+                }
+                rng.shuffle(&mut bits);
+
+                for low in bits {
+                    let mut game_branch = self.clone();
+                    if game_branch.assign_bit(&candidate, low).is_ok() {
+                        if let Some(solution) = game_branch.random_full_solution(rng) {
+                            return Some(solution);
+                        }
+                    }
+                }
+                None
+            },
         }
+    }
 
+    fn render(&self, mask: Candidates) -> String {
+        self.game_setup.spec.symbols.chars().enumerate()
+            .filter(|(i, _)| mask & (1 << i) != 0)
+            .map(|(_, c)| c)
+            .collect()
     }
 }
 
 impl Display for Game<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         let mut square_count = 0;
+        let width = self.game_setup.spec.cols;
         let mut output = String::new();
         for square in &self.game_setup.sorted_squares {
-            output.push_str(
-                &format!("{number:>width$} ", number=self.stats.get(square).unwrap(), width=6));
+            let rendered = self.render(self.stats[self.index(square)]);
+            output.push_str(&format!("{number:>width$} ", number=rendered, width=6));
             square_count += 1;
-            if square_count % 9 == 0 { output.push('\n'); }
+            if square_count % width == 0 { output.push('\n'); }
         }
         write!(f, "{}", output)
     }
@@ -337,15 +616,11 @@ impl Display for SudokuSquare {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         let row =
             match self.0 {
-                0..=8 => (('A' as u8) + (self.0 as u8)) as char,
+                0..=25 => (('A' as u8) + (self.0 as u8)) as char,
                 _ => panic!("Invalid Row Index"),
             };
 
-        let col =
-            match self.1 {
-                0..=8 => self.1 + 1,
-                _ => panic!("Invalid Column Index"),
-            };
+        let col = self.1 + 1;
 
         write!(f, "{}{}", row, col)
     }
@@ -353,11 +628,12 @@ impl Display for SudokuSquare {
 
 fn main() {
 
-    let squares = create_unitlist(0..9, 0..9);
+    let spec = BoardSpec::classic();
+    let squares = create_unitlist(&spec);
     let units = create_unit_dictionary(&squares);
     let peers = create_peers_dictionary(&units);
 
-    let gs = GameSetup::new(&squares, &units, &peers);
+    let gs = GameSetup::new(&spec, &squares, &units, &peers);
 
     let mut game = Game::new(&gs);
     let puzzle = std::fs::read_to_string("sudoku.txt").unwrap();
@@ -365,6 +641,216 @@ fn main() {
 
     // println!("{}", game);
 
-    game.search();
+    println!("unique solution: {}", game.is_unique());
 
+    match game.solve() {
+        Some(solution) => println!("{}", solution),
+        None => println!("No solution."),
+    }
+
+    println!("propagation solve rate: {:.3}", game.propagation_solve_rate());
+    if let Some((_, difficulty)) = game.grade() {
+        println!("{:?}", difficulty);
+    }
+
+    println!("a freshly generated puzzle:\n{}", Game::generate(&gs, 17));
+
+    println!("windoku windows: {}", windoku_units(&spec).len());
+    println!("anti-knight units: {}", knight_move_units(&spec).len());
+
+    let diagonal_extra = diagonal_units(&spec);
+    let mut diagonal_squares = create_unitlist(&spec);
+    diagonal_squares.extend(diagonal_extra.clone());
+    let diagonal_units_dict = create_unit_dictionary(&diagonal_squares);
+    let diagonal_peers = create_peers_dictionary(&diagonal_units_dict);
+    let diagonal_gs = GameSetup::with_extra_units(
+        &spec, &diagonal_squares, &diagonal_units_dict, &diagonal_peers, &diagonal_extra);
+
+    let mut diagonal_game = Game::new(&diagonal_gs);
+    diagonal_game.init_game_with_values(&".".repeat(81));
+    match diagonal_game.solve() {
+        Some(solution) => println!("an X-Sudoku solution:\n{}", solution),
+        None => println!("No solution."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_all(g: &Game<'_>) -> String {
+        g.game_setup.sorted_squares.iter()
+            .map(|sq| g.render(g.stats[g.index(sq)]))
+            .collect()
+    }
+
+    fn first_solution(spec: &BoardSpec, puzzle: &str) -> Option<String> {
+        let squares = create_unitlist(spec);
+        let units = create_unit_dictionary(&squares);
+        let peers = create_peers_dictionary(&units);
+        let gs = GameSetup::new(spec, &squares, &units, &peers);
+        let mut game = Game::new(&gs);
+        game.init_game_with_values(puzzle);
+        game.solve().map(|s| render_all(&s))
+    }
+
+    #[test]
+    fn unit_counts_scale_with_spec() {
+        let s4 = BoardSpec { rows: 4, cols: 4, box_w: 2, box_h: 2, symbols: "1234".into() };
+        assert_eq!(create_unitlist(&s4).len(), 12);
+        let s6 = BoardSpec { rows: 6, cols: 6, box_w: 3, box_h: 2, symbols: "123456".into() };
+        assert_eq!(create_unitlist(&s6).len(), 18);
+        let s16 = BoardSpec { rows: 16, cols: 16, box_w: 4, box_h: 4,
+                              symbols: "0123456789ABCDEF".into() };
+        assert_eq!(create_unitlist(&s16).len(), 48);
+    }
+
+    #[test]
+    fn solves_4x4_and_6x6() {
+        let s4 = BoardSpec { rows: 4, cols: 4, box_w: 2, box_h: 2, symbols: "1234".into() };
+        assert_eq!(first_solution(&s4, ".234341221434321").as_deref(),
+                   Some("1234341221434321"));
+
+        let s6 = BoardSpec { rows: 6, cols: 6, box_w: 3, box_h: 2, symbols: "123456".into() };
+        let full6 = "123456456123231564564231312645645312";
+        assert_eq!(first_solution(&s6, &format!(".{}", &full6[1..])).as_deref(), Some(full6));
+    }
+
+    #[test]
+    fn extra_units_constrain_the_diagonals() {
+        use std::collections::HashSet;
+        let spec = BoardSpec::classic();
+
+        let extra = diagonal_units(&spec);
+        let mut squares = create_unitlist(&spec);
+        squares.extend(extra.clone());
+        let units = create_unit_dictionary(&squares);
+        let peers = create_peers_dictionary(&units);
+        let gs = GameSetup::with_extra_units(&spec, &squares, &units, &peers, &extra);
+
+        let mut game = Game::new(&gs);
+        game.init_game_with_values(&".".repeat(81));
+        let solution = game.solve().expect("an X-Sudoku has a full solution");
+
+        let main_diag: HashSet<String> = (0..9)
+            .map(|i| solution.render(solution.stats[solution.index(&SudokuSquare(i, i))]))
+            .collect();
+        assert_eq!(main_diag.len(), 9);
+    }
+
+    #[test]
+    fn windoku_windows_are_solved_distinct() {
+        use std::collections::HashSet;
+        let spec = BoardSpec::classic();
+        let extra = windoku_units(&spec);
+        assert_eq!(extra.len(), 4);
+
+        let mut squares = create_unitlist(&spec);
+        squares.extend(extra.clone());
+        let units = create_unit_dictionary(&squares);
+        let peers = create_peers_dictionary(&units);
+        let gs = GameSetup::with_extra_units(&spec, &squares, &units, &peers, &extra);
+
+        let mut game = Game::new(&gs);
+        game.init_game_with_values(&".".repeat(81));
+        let solution = game.solve().expect("a Windoku has a full solution");
+
+        for window in &extra {
+            let values: HashSet<String> = window.iter()
+                .map(|sq| solution.render(solution.stats[solution.index(sq)]))
+                .collect();
+            assert_eq!(values.len(), window.len());
+        }
+    }
+
+    #[test]
+    fn knight_move_pairs_are_solved_distinct() {
+        let spec = BoardSpec::classic();
+        let extra = knight_move_units(&spec);
+        assert!(!extra.is_empty());
+
+        let mut squares = create_unitlist(&spec);
+        squares.extend(extra.clone());
+        let units = create_unit_dictionary(&squares);
+        let peers = create_peers_dictionary(&units);
+        let gs = GameSetup::with_extra_units(&spec, &squares, &units, &peers, &extra);
+
+        let mut game = Game::new(&gs);
+        game.init_game_with_values(&".".repeat(81));
+        let solution = game.solve().expect("an anti-knight Sudoku has a full solution");
+
+        for pair in &extra {
+            let values: Vec<String> = pair.iter()
+                .map(|sq| solution.render(solution.stats[solution.index(sq)]))
+                .collect();
+            assert_ne!(values[0], values[1]);
+        }
+    }
+
+    #[test]
+    fn solve_and_is_unique_on_a_known_puzzle() {
+        let spec = BoardSpec::classic();
+        let puzzle = "003020600900305001001806400008102900700000008006708200002609500800203009005010300";
+        let expected = "483921657967345821251876493548132976729564138136798245372689514814253769695417382";
+        assert_eq!(first_solution(&spec, puzzle).as_deref(), Some(expected));
+
+        let squares = create_unitlist(&spec);
+        let units = create_unit_dictionary(&squares);
+        let peers = create_peers_dictionary(&units);
+        let gs = GameSetup::new(&spec, &squares, &units, &peers);
+
+        let mut game = Game::new(&gs);
+        game.init_game_with_values(puzzle);
+        assert!(game.is_unique());
+
+        // An empty board has many solutions, so it is not unique.
+        let mut empty = Game::new(&gs);
+        empty.init_game_with_values(&".".repeat(81));
+        assert!(!empty.is_unique());
+    }
+
+    fn grade_with_symbols(symbols: &str, puzzle: &str) -> Difficulty {
+        let spec = BoardSpec { rows: 9, cols: 9, box_w: 3, box_h: 3, symbols: symbols.into() };
+        let squares = create_unitlist(&spec);
+        let units = create_unit_dictionary(&squares);
+        let peers = create_peers_dictionary(&units);
+        let gs = GameSetup::new(&spec, &squares, &units, &peers);
+        let mut game = Game::new(&gs);
+        game.init_game_with_values(puzzle);
+        game.grade().expect("the puzzle is solvable").1
+    }
+
+    #[test]
+    fn difficulty_is_independent_of_symbol_ordering() {
+        // The same grid, graded with the alphabet permuted three ways.
+        let puzzle = "800000000003600000070090200050007000000045700000100030001000068008500010090000400";
+        let a = grade_with_symbols("123456789", puzzle);
+        let b = grade_with_symbols("987654321", puzzle);
+        let c = grade_with_symbols("193857462", puzzle);
+
+        assert_eq!(a.guesses, b.guesses);
+        assert_eq!(b.guesses, c.guesses);
+        assert_eq!(a.max_depth, b.max_depth);
+        assert_eq!(b.max_depth, c.max_depth);
+        assert!((a.propagation_solve_rate - b.propagation_solve_rate).abs() < 1e-9);
+
+        // Same alphabet, graded repeatedly: peer/unit iteration order must not
+        // depend on the hash-map seed either.
+        let repeat = grade_with_symbols("123456789", puzzle);
+        assert_eq!(a.guesses, repeat.guesses);
+        assert_eq!(a.max_depth, repeat.max_depth);
+    }
+
+    #[test]
+    fn generated_puzzles_are_unique() {
+        let spec = BoardSpec { rows: 4, cols: 4, box_w: 2, box_h: 2, symbols: "1234".into() };
+        let squares = create_unitlist(&spec);
+        let units = create_unit_dictionary(&squares);
+        let peers = create_peers_dictionary(&units);
+        let gs = GameSetup::new(&spec, &squares, &units, &peers);
+
+        let puzzle = Game::generate(&gs, 0);
+        assert!(puzzle.is_unique());
+        assert!(puzzle.solve().is_some());
+    }
 }